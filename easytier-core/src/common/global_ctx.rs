@@ -0,0 +1,60 @@
+use std::sync::Arc;
+
+use tokio::sync::broadcast;
+
+use super::config_fs::ConfigFs;
+use super::netns::NetNS;
+use crate::rpc::PeerConnInfo;
+
+pub type ArcGlobalCtx = Arc<GlobalCtx>;
+
+// capacity of the event bus; slow subscribers lag rather than block issuers.
+const EVENT_BUS_CAP: usize = 100;
+
+/// Events broadcast to anything observing an instance's lifecycle. Conn-level
+/// variants carry the [`PeerConnInfo`] of the conn they concern.
+#[derive(Debug, Clone)]
+pub enum GlobalCtxEvent {
+    TunDeviceReady(String),
+
+    PeerAdded,
+    PeerRemoved,
+
+    PeerConnAdded(PeerConnInfo),
+    PeerConnRemoved(PeerConnInfo),
+    /// A conn crossed the health monitor's degrade threshold.
+    PeerConnDegraded(PeerConnInfo),
+    /// A previously degraded conn recovered below the threshold.
+    PeerConnRecovered(PeerConnInfo),
+}
+
+/// Per-instance shared context: configuration, network namespace and the event
+/// bus every subsystem publishes lifecycle events on.
+pub struct GlobalCtx {
+    pub inst_name: String,
+    pub config_fs: ConfigFs,
+    pub net_ns: NetNS,
+
+    event_bus: broadcast::Sender<GlobalCtxEvent>,
+}
+
+impl GlobalCtx {
+    pub fn new(inst_name: &str, config_fs: ConfigFs, net_ns: NetNS) -> Self {
+        let (event_bus, _) = broadcast::channel(EVENT_BUS_CAP);
+        GlobalCtx {
+            inst_name: inst_name.to_owned(),
+            config_fs,
+            net_ns,
+            event_bus,
+        }
+    }
+
+    /// Publish an event to every subscriber. Dropped if no one is listening.
+    pub fn issue_event(&self, event: GlobalCtxEvent) {
+        let _ = self.event_bus.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<GlobalCtxEvent> {
+        self.event_bus.subscribe()
+    }
+}
@@ -1,13 +1,17 @@
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicU8, AtomicUsize, Ordering};
 use std::sync::Arc;
 
 use dashmap::DashMap;
 
 use tokio::{
     select,
-    sync::{mpsc, Mutex},
+    sync::{mpsc, oneshot, Mutex, OwnedSemaphorePermit, Semaphore},
     task::JoinHandle,
+    time::Instant,
 };
-use tokio_util::bytes::Bytes;
+use tokio_util::bytes::{Bytes, BytesMut};
+use tokio_util::sync::CancellationToken;
 use tracing::Instrument;
 use uuid::Uuid;
 
@@ -19,19 +23,700 @@ use crate::common::{
 use crate::rpc::PeerConnInfo;
 
 type ArcPeerConn = Arc<Mutex<PeerConn>>;
-type ConnMap = Arc<DashMap<Uuid, ArcPeerConn>>;
+type ConnMap = Arc<DashMap<Uuid, ConnEntry>>;
+
+// EWMA weight for the smoothed RTT: new = 7/8 * old + 1/8 * sample.
+const RTT_EWMA_SHIFT: u64 = 3;
+// a conn is considered unhealthy once it accumulates this many consecutive
+// send failures; a single successful send clears the counter.
+const MAX_CONSECUTIVE_FAILURES: u32 = 3;
+// how often the health monitor samples every conn.
+const MONITOR_SAMPLE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+// default thresholds for the health monitor; see [`MonitorConfig`].
+const DEGRADE_RTT_US: u64 = 500_000;
+const DEGRADE_MISS_COUNT: u32 = 3;
+const CLOSE_MISS_COUNT: u32 = 10;
+
+// outgoing messages are split into fixed-size chunks so a bulk transfer can be
+// interleaved with control traffic instead of head-of-line blocking it.
+const CHUNK_SIZE: usize = 16 * 1024;
+// on-wire chunk framing: 8-byte message id + 1-byte flags + 4-byte payload
+// offset, all little endian. The offset lets the receiver reassemble chunks in
+// order even when multipath selection spreads them across conns or a retransmit
+// reorders them.
+const CHUNK_HEADER_LEN: usize = 13;
+const CHUNK_FLAG_FIRST: u8 = 0b01;
+const CHUNK_FLAG_LAST: u8 = 0b10;
+// a partial message is dropped if it has not completed within this long (a lost
+// LAST chunk would otherwise pin its buffer forever), and no more than this many
+// partials are kept at once.
+const PARTIAL_TTL_MS: u64 = 30_000;
+const MAX_PARTIAL_MSGS: usize = 1024;
+// how many recently completed message ids are remembered so a duplicate copy
+// produced by `SelectionPolicy::Redundant` is dropped instead of delivered
+// twice up the stack.
+const SEEN_MSG_CAP: usize = 4096;
+// depth of each per-priority queue; a full queue applies backpressure to the
+// caller of `send_msg_with_priority`.
+const SEND_QUEUE_DEPTH: usize = 1024;
+// how long a conn is given to flush in-flight writes once it starts draining
+// before it is removed regardless.
+const DRAIN_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+// how often the drain barrier re-checks whether the send queues have emptied.
+const DRAIN_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(20);
+
+// every message carries a leading kind byte so the receive side can tell plain
+// traffic from the reliable-delivery frames layered on top of it. Acks are not
+// a message kind: they are piggybacked on the pingpong channel, never the data
+// plane.
+const KIND_PLAIN: u8 = 0;
+const KIND_RELIABLE: u8 = 1;
+
+// upper bound on outstanding unacked reliable messages; once reached,
+// `send_msg_reliable` blocks, applying backpressure to the caller.
+const MAX_IN_FLIGHT_RELIABLE: usize = 256;
+// how often the retransmit task revisits the in-flight buffer.
+const RETRANSMIT_TICK: std::time::Duration = std::time::Duration::from_millis(100);
+// floor/ceiling/fallback for the RTT-derived retransmit timeout.
+const RELIABLE_TIMEOUT_MIN: std::time::Duration = std::time::Duration::from_millis(100);
+const RELIABLE_TIMEOUT_MAX: std::time::Duration = std::time::Duration::from_secs(2);
+const RELIABLE_TIMEOUT_DEFAULT: std::time::Duration = std::time::Duration::from_millis(500);
+// overall deadline for a `send_msg_reliable`; if no ack arrives within this the
+// message is abandoned and the caller gets an error rather than hanging forever
+// on a conn that died permanently.
+const RELIABLE_SEND_DEADLINE: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Policy controlling how [`Peer::send_msg`] spreads traffic across the
+/// connections a peer currently holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelectionPolicy {
+    /// Always use the healthy conn with the lowest smoothed RTT.
+    LowestLatency,
+    /// Rotate across the healthy conns so load is spread evenly.
+    RoundRobin,
+    /// Duplicate the packet across the two best conns, for loss-sensitive flows.
+    Redundant,
+}
+
+impl Default for SelectionPolicy {
+    fn default() -> Self {
+        SelectionPolicy::LowestLatency
+    }
+}
+
+impl SelectionPolicy {
+    fn as_u8(self) -> u8 {
+        match self {
+            SelectionPolicy::LowestLatency => 0,
+            SelectionPolicy::RoundRobin => 1,
+            SelectionPolicy::Redundant => 2,
+        }
+    }
+
+    fn from_u8(v: u8) -> Self {
+        match v {
+            1 => SelectionPolicy::RoundRobin,
+            2 => SelectionPolicy::Redundant,
+            _ => SelectionPolicy::LowestLatency,
+        }
+    }
+}
+
+/// Thresholds driving the per-peer health monitor. A conn whose smoothed RTT or
+/// consecutive bad samples cross these bounds is reported degraded/recovered and
+/// ultimately auto-closed.
+#[derive(Debug, Clone, Copy)]
+pub struct MonitorConfig {
+    /// Smoothed RTT (micros) above which a sample counts as bad.
+    pub degrade_rtt_us: u64,
+    /// Consecutive bad samples before a conn is flagged degraded.
+    pub degrade_miss_count: u32,
+    /// Consecutive bad samples before a conn is auto-closed.
+    pub close_miss_count: u32,
+    /// How often each conn is sampled.
+    pub sample_interval: std::time::Duration,
+}
+
+impl Default for MonitorConfig {
+    fn default() -> Self {
+        Self {
+            degrade_rtt_us: DEGRADE_RTT_US,
+            degrade_miss_count: DEGRADE_MISS_COUNT,
+            close_miss_count: CLOSE_MISS_COUNT,
+            sample_interval: MONITOR_SAMPLE_INTERVAL,
+        }
+    }
+}
+
+/// Priority class for an outgoing message. The per-peer writer always drains
+/// the highest-priority non-empty queue first, so a bulk transfer never blocks
+/// a high-priority packet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Priority {
+    High,
+    Normal,
+    Bulk,
+}
+
+impl Priority {
+    // index into the writer's per-priority queue array, highest first.
+    fn idx(self) -> usize {
+        match self {
+            Priority::High => 0,
+            Priority::Normal => 1,
+            Priority::Bulk => 2,
+        }
+    }
+}
+
+// Frame a single chunk's payload behind an 8-byte message id, a flags byte and
+// the payload's byte offset within the whole message.
+fn encode_chunk(msg_id: u64, flags: u8, offset: u32, payload: &[u8]) -> Bytes {
+    let mut buf = BytesMut::with_capacity(CHUNK_HEADER_LEN + payload.len());
+    buf.extend_from_slice(&msg_id.to_le_bytes());
+    buf.extend_from_slice(&[flags]);
+    buf.extend_from_slice(&offset.to_le_bytes());
+    buf.extend_from_slice(payload);
+    buf.freeze()
+}
+
+// Prepend the kind byte to a plain application message.
+fn frame_plain(msg: &Bytes) -> Bytes {
+    let mut buf = BytesMut::with_capacity(1 + msg.len());
+    buf.extend_from_slice(&[KIND_PLAIN]);
+    buf.extend_from_slice(msg);
+    buf.freeze()
+}
+
+// Wrap an application message as a reliable frame tagged with its sequence.
+fn frame_reliable(seq: u64, msg: &Bytes) -> Bytes {
+    let mut buf = BytesMut::with_capacity(1 + 8 + msg.len());
+    buf.extend_from_slice(&[KIND_RELIABLE]);
+    buf.extend_from_slice(&seq.to_le_bytes());
+    buf.extend_from_slice(msg);
+    buf.freeze()
+}
+
+// Split `msg` into framed chunks tagged with a shared message id; a single
+// short message yields one chunk carrying both the first and last flag.
+fn chunk_msg(msg_id: u64, msg: &Bytes) -> Vec<Bytes> {
+    if msg.is_empty() {
+        return vec![encode_chunk(msg_id, CHUNK_FLAG_FIRST | CHUNK_FLAG_LAST, 0, &[])];
+    }
+    let mut chunks = Vec::new();
+    let total = msg.len();
+    let mut offset = 0;
+    while offset < total {
+        let end = (offset + CHUNK_SIZE).min(total);
+        let mut flags = 0;
+        if offset == 0 {
+            flags |= CHUNK_FLAG_FIRST;
+        }
+        if end == total {
+            flags |= CHUNK_FLAG_LAST;
+        }
+        chunks.push(encode_chunk(msg_id, flags, offset as u32, &msg[offset..end]));
+        offset = end;
+    }
+    chunks
+}
+
+// An in-progress message being rebuilt from its chunks.
+struct PartialMsg {
+    // payload fragments keyed by their byte offset, so chunks that arrive out of
+    // order -- because selection spread them across conns or a retransmit
+    // reordered them -- still reassemble correctly.
+    chunks: BTreeMap<u32, Bytes>,
+    // total message length, known once the LAST chunk has been observed.
+    total: Option<usize>,
+    // arrival time of the first fragment (monitor-epoch millis) for TTL eviction.
+    created_ms: u64,
+}
+
+// Reassembles framed chunks into whole messages. Chunks of one message may
+// arrive over several conns and out of order, so fragments are buffered by
+// offset and only emitted once the message is contiguous and complete. Stale
+// partials (e.g. left behind by a lost LAST chunk) are evicted by TTL and a
+// hard cap so the buffer cannot grow without bound.
+struct Reassembler {
+    partial: HashMap<u64, PartialMsg>,
+    // ids of recently completed messages, used to drop redundant duplicates.
+    seen: HashSet<u64>,
+    seen_order: VecDeque<u64>,
+}
+
+impl Reassembler {
+    fn new() -> Self {
+        Self {
+            partial: HashMap::new(),
+            seen: HashSet::new(),
+            seen_order: VecDeque::new(),
+        }
+    }
+
+    // Remember a completed message id, evicting the oldest once the window fills.
+    fn mark_seen(&mut self, msg_id: u64) {
+        if self.seen.insert(msg_id) {
+            self.seen_order.push_back(msg_id);
+            if self.seen_order.len() > SEEN_MSG_CAP {
+                if let Some(old) = self.seen_order.pop_front() {
+                    self.seen.remove(&old);
+                }
+            }
+        }
+    }
+
+    // Drop partials that have outlived `PARTIAL_TTL_MS`, then, if a new partial
+    // for `incoming` would exceed the cap, drop the oldest ones to make room.
+    fn evict_stale(&mut self, now_ms: u64, incoming: u64) {
+        self.partial
+            .retain(|_, p| now_ms.saturating_sub(p.created_ms) < PARTIAL_TTL_MS);
+        let room = if self.partial.contains_key(&incoming) { 0 } else { 1 };
+        while self.partial.len() + room > MAX_PARTIAL_MSGS {
+            if let Some((&oldest, _)) = self.partial.iter().min_by_key(|(_, p)| p.created_ms) {
+                self.partial.remove(&oldest);
+            } else {
+                break;
+            }
+        }
+    }
+
+    // Feed one framed chunk in; returns the completed message (kind byte still
+    // attached) once every fragment has arrived, otherwise `None`.
+    fn push(&mut self, chunk: Bytes, now_ms: u64) -> Option<Bytes> {
+        if chunk.len() < CHUNK_HEADER_LEN {
+            return None;
+        }
+        let msg_id = u64::from_le_bytes(chunk[0..8].try_into().unwrap());
+        let flags = chunk[8];
+        let offset = u32::from_le_bytes(chunk[9..13].try_into().unwrap());
+        let payload = chunk.slice(CHUNK_HEADER_LEN..);
+
+        // a redundant copy of a message we already delivered: drop it.
+        if self.seen.contains(&msg_id) {
+            return None;
+        }
+
+        self.evict_stale(now_ms, msg_id);
+
+        let entry = self.partial.entry(msg_id).or_insert_with(|| PartialMsg {
+            chunks: BTreeMap::new(),
+            total: None,
+            created_ms: now_ms,
+        });
+        let payload_len = payload.len();
+        entry.chunks.insert(offset, payload);
+        if flags & CHUNK_FLAG_LAST != 0 {
+            entry.total = Some(offset as usize + payload_len);
+        }
+
+        // only complete once the LAST chunk is in and the fragments tile the
+        // whole message with no gaps.
+        let total = entry.total?;
+        let mut assembled = BytesMut::with_capacity(total);
+        for (off, buf) in entry.chunks.iter() {
+            if *off as usize != assembled.len() {
+                return None;
+            }
+            assembled.extend_from_slice(buf);
+        }
+        if assembled.len() != total {
+            return None;
+        }
+
+        self.partial.remove(&msg_id);
+        self.mark_seen(msg_id);
+        Some(assembled.freeze())
+    }
+}
+
+// Per-connection quality tracked alongside the conn so selection can avoid
+// slow or flaky links without locking the conn itself.
+struct ConnQuality {
+    // smoothed RTT in micros; u64::MAX until the first sample is observed so
+    // that an unmeasured conn sorts after any measured one.
+    smoothed_rtt_us: AtomicU64,
+    consecutive_failures: AtomicU32,
+
+    // live stats maintained by the health monitor.
+    tx_bytes: AtomicU64,
+    rx_bytes: AtomicU64,
+    last_active_ms: AtomicU64,
+    consecutive_misses: AtomicU32,
+    degraded: AtomicBool,
+}
+
+impl ConnQuality {
+    fn new() -> Self {
+        Self {
+            smoothed_rtt_us: AtomicU64::new(u64::MAX),
+            consecutive_failures: AtomicU32::new(0),
+
+            tx_bytes: AtomicU64::new(0),
+            rx_bytes: AtomicU64::new(0),
+            last_active_ms: AtomicU64::new(0),
+            consecutive_misses: AtomicU32::new(0),
+            degraded: AtomicBool::new(false),
+        }
+    }
+
+    // Fold the latest byte counters in, stamping last-activity whenever either
+    // direction advanced since the previous sample.
+    fn note_bytes(&self, tx: u64, rx: u64, now_ms: u64) {
+        let prev_tx = self.tx_bytes.swap(tx, Ordering::Relaxed);
+        let prev_rx = self.rx_bytes.swap(rx, Ordering::Relaxed);
+        if tx != prev_tx || rx != prev_rx {
+            self.last_active_ms.store(now_ms, Ordering::Relaxed);
+        }
+    }
+
+    fn bump_miss(&self) -> u32 {
+        self.consecutive_misses.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    fn reset_miss(&self) {
+        self.consecutive_misses.store(0, Ordering::Relaxed);
+    }
+
+    fn set_degraded(&self, v: bool) {
+        self.degraded.store(v, Ordering::Relaxed);
+    }
+
+    fn is_degraded(&self) -> bool {
+        self.degraded.load(Ordering::Relaxed)
+    }
+
+    fn record_rtt(&self, sample_us: u64) {
+        let prev = self.smoothed_rtt_us.load(Ordering::Relaxed);
+        let next = if prev == u64::MAX {
+            sample_us
+        } else {
+            prev - (prev >> RTT_EWMA_SHIFT) + (sample_us >> RTT_EWMA_SHIFT)
+        };
+        self.smoothed_rtt_us.store(next, Ordering::Relaxed);
+    }
+
+    fn record_success(&self) {
+        self.consecutive_failures.store(0, Ordering::Relaxed);
+    }
+
+    fn record_failure(&self) {
+        self.consecutive_failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn is_healthy(&self) -> bool {
+        self.consecutive_failures.load(Ordering::Relaxed) < MAX_CONSECUTIVE_FAILURES
+    }
+
+    fn smoothed_rtt_us(&self) -> u64 {
+        self.smoothed_rtt_us.load(Ordering::Relaxed)
+    }
+
+    // Overlay the live quality stats onto a conn's info so `list_peer_conns`
+    // exposes smoothed RTT, byte counters and health to its callers.
+    fn fill_info(&self, info: &mut PeerConnInfo) {
+        let rtt = self.smoothed_rtt_us.load(Ordering::Relaxed);
+        info.stats.smoothed_rtt_us = if rtt == u64::MAX { 0 } else { rtt };
+        info.stats.tx_bytes = self.tx_bytes.load(Ordering::Relaxed);
+        info.stats.rx_bytes = self.rx_bytes.load(Ordering::Relaxed);
+        info.stats.consecutive_misses = self.consecutive_misses.load(Ordering::Relaxed);
+        info.stats.degraded = self.is_degraded();
+    }
+}
+
+#[derive(Clone)]
+struct ConnEntry {
+    conn: ArcPeerConn,
+    quality: Arc<ConnQuality>,
+    // set once the conn begins a graceful teardown; selection skips it while it
+    // flushes whatever is still in flight.
+    draining: Arc<AtomicBool>,
+}
+
+impl ConnEntry {
+    fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::Relaxed)
+    }
+}
+
+// Shared send state. The per-peer writer task and the reliable retransmit task
+// both route packets through this dispatcher, and the `send_msg*` entry points
+// use it for their pre-flight conn check, so selection and quality accounting
+// stay consistent.
+struct Dispatcher {
+    peer_node_id: Uuid,
+    conns: ConnMap,
+    default_policy: AtomicU8,
+    round_robin_idx: AtomicUsize,
+}
+
+impl Dispatcher {
+    fn policy(&self) -> SelectionPolicy {
+        SelectionPolicy::from_u8(self.default_policy.load(Ordering::Relaxed))
+    }
+
+    async fn send_with_policy(&self, msg: Bytes, policy: SelectionPolicy) -> Result<(), Error> {
+        let mut candidates = self.healthy_conns_by_latency();
+        if candidates.is_empty() {
+            // every conn looks unhealthy right now; fall back to whatever we
+            // have (except conns that are draining) rather than dropping the
+            // packet outright.
+            candidates = self
+                .conns
+                .iter()
+                .map(|c| c.value().clone())
+                .filter(|e| !e.is_draining())
+                .collect();
+        }
+        if candidates.is_empty() {
+            return Err(Error::PeerNoConnectionError(self.peer_node_id));
+        }
+
+        match policy {
+            SelectionPolicy::LowestLatency => self.send_in_order(candidates, msg).await,
+            SelectionPolicy::RoundRobin => {
+                let start =
+                    self.round_robin_idx.fetch_add(1, Ordering::Relaxed) % candidates.len();
+                candidates.rotate_left(start);
+                self.send_in_order(candidates, msg).await
+            }
+            SelectionPolicy::Redundant => self.send_redundant(candidates, msg).await,
+        }
+    }
+
+    // Whether the peer currently has any conn that could carry a packet (i.e.
+    // not draining). Used for a synchronous pre-flight check so a send on a peer
+    // with no usable conn fails the caller instead of being silently dropped by
+    // the writer task.
+    fn has_usable_conn(&self) -> bool {
+        self.conns.iter().any(|c| !c.value().is_draining())
+    }
+
+    // Lowest smoothed RTT (micros) among the conns eligible for sending, if any
+    // has been measured yet. Used to derive the reliable retransmit timeout.
+    fn best_rtt_us(&self) -> Option<u64> {
+        self.conns
+            .iter()
+            .filter(|c| c.value().quality.is_healthy() && !c.value().is_draining())
+            .map(|c| c.value().quality.smoothed_rtt_us())
+            .filter(|r| *r != u64::MAX)
+            .min()
+    }
+
+    // Collect the healthy conns sorted from lowest to highest smoothed RTT.
+    fn healthy_conns_by_latency(&self) -> Vec<ConnEntry> {
+        let mut candidates: Vec<ConnEntry> = self
+            .conns
+            .iter()
+            .map(|c| c.value().clone())
+            .filter(|e| e.quality.is_healthy() && !e.is_draining())
+            .collect();
+        candidates.sort_by_key(|e| e.quality.smoothed_rtt_us());
+        candidates
+    }
+
+    // Try each candidate in turn, retrying on the next-best conn whenever a
+    // send fails, before surfacing the error.
+    async fn send_in_order(&self, candidates: Vec<ConnEntry>, msg: Bytes) -> Result<(), Error> {
+        let mut last_err = None;
+        for entry in candidates {
+            match entry.conn.lock().await.send_msg(msg.clone()).await {
+                Ok(()) => {
+                    entry.quality.record_success();
+                    return Ok(());
+                }
+                Err(e) => {
+                    entry.quality.record_failure();
+                    last_err = Some(e);
+                }
+            }
+        }
+        Err(last_err.unwrap_or(Error::PeerNoConnectionError(self.peer_node_id)))
+    }
+
+    // Duplicate the packet across the two best conns; the send is considered to
+    // have succeeded as long as one copy made it out.
+    async fn send_redundant(&self, candidates: Vec<ConnEntry>, msg: Bytes) -> Result<(), Error> {
+        let mut last_err = None;
+        let mut any_ok = false;
+        for entry in candidates.into_iter().take(2) {
+            match entry.conn.lock().await.send_msg(msg.clone()).await {
+                Ok(()) => {
+                    entry.quality.record_success();
+                    any_ok = true;
+                }
+                Err(e) => {
+                    entry.quality.record_failure();
+                    last_err = Some(e);
+                }
+            }
+        }
+        if any_ok {
+            Ok(())
+        } else {
+            Err(last_err.unwrap_or(Error::PeerNoConnectionError(self.peer_node_id)))
+        }
+    }
+}
+
+// Per-priority send queues feeding the writer task. Queues are bounded so a
+// large bulk transfer cannot grow memory without bound.
+struct SendScheduler {
+    queues: [mpsc::Sender<Bytes>; 3],
+    next_msg_id: AtomicU64,
+}
+
+impl SendScheduler {
+    // Split a framed message into wire chunks under a fresh message id.
+    fn chunk(&self, msg: &Bytes) -> Vec<Bytes> {
+        let msg_id = self.next_msg_id.fetch_add(1, Ordering::Relaxed);
+        chunk_msg(msg_id, msg)
+    }
+
+    // Whether every priority queue is empty, i.e. the writer has drained all
+    // accepted chunks. `capacity() == max_capacity()` means no queued items.
+    fn is_drained(&self) -> bool {
+        self.queues
+            .iter()
+            .all(|q| q.capacity() == q.max_capacity())
+    }
+
+    async fn enqueue(&self, prio: Priority, msg: Bytes) -> Result<(), Error> {
+        let queue = &self.queues[prio.idx()];
+        for chunk in self.chunk(&msg) {
+            queue
+                .send(chunk)
+                .await
+                .map_err(|_| Error::PeerNoConnectionError(Uuid::nil()))?;
+        }
+        Ok(())
+    }
+}
+
+// A reliable message awaiting acknowledgement. The frame is retained so it can
+// be retransmitted, and the waiter is fired once the cumulative ack arrives.
+struct InFlight {
+    frame: Bytes,
+    last_sent: Instant,
+    waiter: Option<oneshot::Sender<()>>,
+    // released on drop, freeing a slot in the in-flight window.
+    _permit: OwnedSemaphorePermit,
+}
+
+// Receive-side bookkeeping for in-order delivery and cumulative acknowledgement.
+struct RecvState {
+    // highest sequence delivered to the owner with no gap below it.
+    watermark: u64,
+    // payloads received ahead of the watermark, held back until the gap below
+    // them fills so the stream is delivered in order.
+    out_of_order: BTreeMap<u64, Bytes>,
+}
+
+// Optional reliable-delivery layer. Reliable messages get a monotonic sequence
+// number and are held in an in-flight buffer until the remote acks them;
+// unacked messages are retransmitted on a different healthy conn.
+struct ReliableState {
+    next_seq: AtomicU64,
+    in_flight: Mutex<BTreeMap<u64, InFlight>>,
+    permits: Arc<Semaphore>,
+    recv: Mutex<RecvState>,
+    // the cumulative ack we owe the remote, shared with every conn's pingpong
+    // task so it can be piggybacked on the next ping.
+    outbound_ack: Arc<AtomicU64>,
+}
+
+impl ReliableState {
+    fn new() -> Self {
+        Self {
+            next_seq: AtomicU64::new(1),
+            in_flight: Mutex::new(BTreeMap::new()),
+            permits: Arc::new(Semaphore::new(MAX_IN_FLIGHT_RELIABLE)),
+            recv: Mutex::new(RecvState {
+                watermark: 0,
+                out_of_order: BTreeMap::new(),
+            }),
+            outbound_ack: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    // Record the cumulative ack to piggyback on the next outgoing ping.
+    fn set_outbound_ack(&self, cum: u64) {
+        self.outbound_ack.store(cum, Ordering::Relaxed);
+    }
+
+    // Acknowledge every in-flight sequence up to and including `cum`, waking the
+    // corresponding `send_msg_reliable` futures and freeing their window slots.
+    async fn process_ack(&self, cum: u64) {
+        let mut map = self.in_flight.lock().await;
+        let acked: Vec<u64> = map.range(..=cum).map(|(k, _)| *k).collect();
+        for seq in acked {
+            if let Some(mut f) = map.remove(&seq) {
+                if let Some(w) = f.waiter.take() {
+                    let _ = w.send(());
+                }
+            }
+        }
+    }
+
+    // Record an incoming reliable sequence and its payload. Returns the new
+    // cumulative ack watermark and the payloads that are now deliverable in
+    // order -- empty for a duplicate or for a sequence still ahead of a gap, and
+    // more than one when this sequence fills a gap and flushes buffered ones.
+    async fn on_received(&self, seq: u64, payload: Bytes) -> (u64, Vec<Bytes>) {
+        let mut r = self.recv.lock().await;
+        let mut deliver = Vec::new();
+        if seq == r.watermark + 1 {
+            r.watermark += 1;
+            deliver.push(payload);
+            while let Some(p) = r.out_of_order.remove(&(r.watermark + 1)) {
+                r.watermark += 1;
+                deliver.push(p);
+            }
+        } else if seq > r.watermark + 1 {
+            // ahead of the watermark: buffer until the gap below it fills.
+            r.out_of_order.insert(seq, payload);
+        }
+        // else: a duplicate at or below the watermark, already delivered.
+        (r.watermark, deliver)
+    }
+}
+
+// Derive the retransmit timeout from the best observed RTT, clamped to sane
+// bounds; falls back to a default before any RTT has been measured.
+fn reliable_timeout(best_rtt_us: Option<u64>) -> std::time::Duration {
+    best_rtt_us
+        .map(|r| std::time::Duration::from_micros(r.saturating_mul(3)))
+        .unwrap_or(RELIABLE_TIMEOUT_DEFAULT)
+        .clamp(RELIABLE_TIMEOUT_MIN, RELIABLE_TIMEOUT_MAX)
+}
 
 pub struct Peer {
     pub peer_node_id: uuid::Uuid,
-    conns: ConnMap,
     global_ctx: ArcGlobalCtx,
 
-    packet_recv_chan: mpsc::Sender<Bytes>,
+    dispatcher: Arc<Dispatcher>,
+    scheduler: Arc<SendScheduler>,
+    reliable: Arc<ReliableState>,
+
+    drain_timeout: std::time::Duration,
+
+    // chunks arriving from every conn land here and are reassembled before the
+    // completed message is forwarded to the owner's `packet_recv_chan`.
+    chunk_recv_chan: mpsc::Sender<Bytes>,
+
+    // acks the remote piggybacks on pongs flow in here from each conn's
+    // pingpong task and are applied to the in-flight buffer by `ack_task`.
+    ack_event_sender: mpsc::Sender<u64>,
 
     close_event_sender: mpsc::Sender<Uuid>,
     close_event_listener: JoinHandle<()>,
+    writer_task: JoinHandle<()>,
+    reassemble_task: JoinHandle<()>,
+    retransmit_task: JoinHandle<()>,
+    monitor_task: JoinHandle<()>,
+    ack_task: JoinHandle<()>,
 
-    shutdown_notifier: Arc<tokio::sync::Notify>,
+    shutdown: CancellationToken,
 }
 
 impl Peer {
@@ -42,10 +727,17 @@ impl Peer {
     ) -> Self {
         let conns: ConnMap = Arc::new(DashMap::new());
         let (close_event_sender, mut close_event_receiver) = mpsc::channel(10);
-        let shutdown_notifier = Arc::new(tokio::sync::Notify::new());
+        let shutdown = CancellationToken::new();
+
+        let dispatcher = Arc::new(Dispatcher {
+            peer_node_id,
+            conns: conns.clone(),
+            default_policy: AtomicU8::new(SelectionPolicy::default().as_u8()),
+            round_robin_idx: AtomicUsize::new(0),
+        });
 
         let conns_copy = conns.clone();
-        let shutdown_notifier_copy = shutdown_notifier.clone();
+        let shutdown_copy = shutdown.clone();
         let global_ctx_copy = global_ctx.clone();
         let close_event_listener = tokio::spawn(
             async move {
@@ -62,14 +754,14 @@ impl Peer {
                                 "notified that peer conn is closed",
                             );
 
-                            if let Some((_, conn)) = conns_copy.remove(&ret) {
+                            if let Some((_, entry)) = conns_copy.remove(&ret) {
                                 global_ctx_copy.issue_event(GlobalCtxEvent::PeerConnRemoved(
-                                    conn.lock().await.get_conn_info(),
+                                    entry.conn.lock().await.get_conn_info(),
                                 ));
                             }
                         }
 
-                        _ = shutdown_notifier_copy.notified() => {
+                        _ = shutdown_copy.cancelled() => {
                             close_event_receiver.close();
                             tracing::warn!(?peer_node_id, "peer close event listener notified");
                         }
@@ -83,60 +775,507 @@ impl Peer {
             )),
         );
 
-        Peer {
+        // per-priority send queues drained by a single writer task.
+        let (high_tx, high_rx) = mpsc::channel(SEND_QUEUE_DEPTH);
+        let (normal_tx, normal_rx) = mpsc::channel(SEND_QUEUE_DEPTH);
+        let (bulk_tx, bulk_rx) = mpsc::channel(SEND_QUEUE_DEPTH);
+        let scheduler = Arc::new(SendScheduler {
+            queues: [high_tx, normal_tx, bulk_tx],
+            next_msg_id: AtomicU64::new(0),
+        });
+
+        let writer_task = Self::spawn_writer(
             peer_node_id,
-            conns: conns.clone(),
+            dispatcher.clone(),
+            high_rx,
+            normal_rx,
+            bulk_rx,
+            shutdown.clone(),
+        );
+
+        let reliable = Arc::new(ReliableState::new());
+
+        // incoming chunks are reassembled into whole messages here.
+        let (chunk_recv_chan, chunk_recv_rx) = mpsc::channel(SEND_QUEUE_DEPTH);
+        let reassemble_task = Self::spawn_reassembler(
+            peer_node_id,
+            chunk_recv_rx,
             packet_recv_chan,
+            reliable.clone(),
+            shutdown.clone(),
+        );
+
+        // remote acks, echoed on pongs, are applied to the in-flight buffer here.
+        let (ack_event_sender, ack_event_receiver) = mpsc::channel(SEND_QUEUE_DEPTH);
+        let ack_task = Self::spawn_ack_reader(
+            peer_node_id,
+            ack_event_receiver,
+            reliable.clone(),
+            shutdown.clone(),
+        );
+
+        let retransmit_task = Self::spawn_retransmit(
+            peer_node_id,
+            dispatcher.clone(),
+            scheduler.clone(),
+            reliable.clone(),
+            shutdown.clone(),
+        );
+
+        let monitor_task = Self::spawn_monitor(
+            peer_node_id,
+            dispatcher.clone(),
+            global_ctx.clone(),
+            close_event_sender.clone(),
+            MonitorConfig::default(),
+            shutdown.clone(),
+        );
+
+        Peer {
+            peer_node_id,
             global_ctx,
 
+            dispatcher,
+            scheduler,
+            reliable,
+
+            drain_timeout: DRAIN_TIMEOUT,
+
+            chunk_recv_chan,
+
+            ack_event_sender,
+
             close_event_sender,
             close_event_listener,
+            writer_task,
+            reassemble_task,
+            retransmit_task,
+            monitor_task,
+            ack_task,
 
-            shutdown_notifier,
+            shutdown,
         }
     }
 
+    // Drain the highest-priority non-empty queue one chunk at a time. Because a
+    // single chunk is emitted per loop iteration, a high-priority packet queued
+    // mid-transfer preempts the remainder of a bulk message.
+    fn spawn_writer(
+        peer_node_id: Uuid,
+        dispatcher: Arc<Dispatcher>,
+        mut high_rx: mpsc::Receiver<Bytes>,
+        mut normal_rx: mpsc::Receiver<Bytes>,
+        mut bulk_rx: mpsc::Receiver<Bytes>,
+        shutdown: CancellationToken,
+    ) -> JoinHandle<()> {
+        tokio::spawn(
+            async move {
+                loop {
+                    let chunk = select! {
+                        biased;
+
+                        _ = shutdown.cancelled() => break,
+                        Some(chunk) = high_rx.recv() => chunk,
+                        Some(chunk) = normal_rx.recv() => chunk,
+                        Some(chunk) = bulk_rx.recv() => chunk,
+                        else => break,
+                    };
+
+                    let policy = dispatcher.policy();
+                    if let Err(e) = dispatcher.send_with_policy(chunk, policy).await {
+                        tracing::warn!(?peer_node_id, ?e, "peer writer failed to send chunk");
+                    }
+                }
+                tracing::info!("peer {} writer task exit", peer_node_id);
+            }
+            .instrument(tracing::info_span!("peer_writer", ?peer_node_id)),
+        )
+    }
+
+    // Reassemble framed chunks by message id and forward the completed `Bytes`
+    // to the peer owner's receive channel.
+    fn spawn_reassembler(
+        peer_node_id: Uuid,
+        mut chunk_rx: mpsc::Receiver<Bytes>,
+        packet_recv_chan: mpsc::Sender<Bytes>,
+        reliable: Arc<ReliableState>,
+        shutdown: CancellationToken,
+    ) -> JoinHandle<()> {
+        tokio::spawn(
+            async move {
+                let mut reasm = Reassembler::new();
+                let epoch = Instant::now();
+                loop {
+                    let chunk = select! {
+                        _ = shutdown.cancelled() => break,
+                        ret = chunk_rx.recv() => match ret {
+                            Some(chunk) => chunk,
+                            None => break,
+                        },
+                    };
+
+                    let now_ms = epoch.elapsed().as_millis() as u64;
+                    let Some(msg) = reasm.push(chunk, now_ms) else {
+                        continue;
+                    };
+                    if msg.is_empty() {
+                        continue;
+                    }
+
+                    // strip the kind byte added by the send-side framing.
+                    match msg[0] {
+                        KIND_PLAIN => {
+                            if packet_recv_chan.send(msg.slice(1..)).await.is_err() {
+                                break;
+                            }
+                        }
+                        KIND_RELIABLE => {
+                            if msg.len() < 9 {
+                                tracing::warn!(?peer_node_id, "truncated reliable frame");
+                                continue;
+                            }
+                            let seq = u64::from_le_bytes(msg[1..9].try_into().unwrap());
+                            // advance the cumulative ack and hand it to the
+                            // pingpong layer, which piggybacks it on the next
+                            // ping; deliver payloads strictly in sequence order,
+                            // buffering any that arrive ahead of a gap. Incoming
+                            // acks arrive the same way -- echoed on pongs -- and
+                            // are fed to `process_ack` by the ack reader task, so
+                            // no ack ever rides the data plane.
+                            let (cum, deliver) = reliable.on_received(seq, msg.slice(9..)).await;
+                            reliable.set_outbound_ack(cum);
+                            let mut closed = false;
+                            for payload in deliver {
+                                if packet_recv_chan.send(payload).await.is_err() {
+                                    closed = true;
+                                    break;
+                                }
+                            }
+                            if closed {
+                                break;
+                            }
+                        }
+                        other => {
+                            tracing::warn!(?peer_node_id, other, "unknown message kind, dropping");
+                        }
+                    }
+                }
+                tracing::info!("peer {} reassemble task exit", peer_node_id);
+            }
+            .instrument(tracing::info_span!("peer_reassembler", ?peer_node_id)),
+        )
+    }
+
+    // Drain the cumulative acks the remote piggybacks on pongs and apply them to
+    // the in-flight buffer, releasing the `send_msg_reliable` waiters they cover.
+    fn spawn_ack_reader(
+        peer_node_id: Uuid,
+        mut ack_rx: mpsc::Receiver<u64>,
+        reliable: Arc<ReliableState>,
+        shutdown: CancellationToken,
+    ) -> JoinHandle<()> {
+        tokio::spawn(
+            async move {
+                loop {
+                    let cum = select! {
+                        _ = shutdown.cancelled() => break,
+                        ret = ack_rx.recv() => match ret {
+                            Some(cum) => cum,
+                            None => break,
+                        },
+                    };
+                    reliable.process_ack(cum).await;
+                }
+                tracing::info!("peer {} ack reader task exit", peer_node_id);
+            }
+            .instrument(tracing::info_span!("peer_ack_reader", ?peer_node_id)),
+        )
+    }
+
+    // Periodically retransmit reliable messages whose ack is overdue. Resends go
+    // out with the redundant policy so a message that presumably failed on one
+    // link is duplicated across the two best healthy conns.
+    fn spawn_retransmit(
+        peer_node_id: Uuid,
+        dispatcher: Arc<Dispatcher>,
+        scheduler: Arc<SendScheduler>,
+        reliable: Arc<ReliableState>,
+        shutdown: CancellationToken,
+    ) -> JoinHandle<()> {
+        tokio::spawn(
+            async move {
+                loop {
+                    select! {
+                        _ = shutdown.cancelled() => break,
+                        _ = tokio::time::sleep(RETRANSMIT_TICK) => {}
+                    }
+
+                    let timeout = reliable_timeout(dispatcher.best_rtt_us());
+                    let now = Instant::now();
+                    let mut due = Vec::new();
+                    {
+                        let mut map = reliable.in_flight.lock().await;
+                        for (_, f) in map.iter_mut() {
+                            if now.duration_since(f.last_sent) >= timeout {
+                                due.push(f.frame.clone());
+                                f.last_sent = now;
+                            }
+                        }
+                    }
+                    for frame in due {
+                        for chunk in scheduler.chunk(&frame) {
+                            if let Err(e) = dispatcher
+                                .send_with_policy(chunk, SelectionPolicy::Redundant)
+                                .await
+                            {
+                                tracing::warn!(?peer_node_id, ?e, "reliable retransmit failed");
+                            }
+                        }
+                    }
+                }
+                tracing::info!("peer {} retransmit task exit", peer_node_id);
+            }
+            .instrument(tracing::info_span!("peer_retransmit", ?peer_node_id)),
+        )
+    }
+
+    // Sample every conn on a fixed cadence, feeding the RTT EWMA and byte/
+    // activity stats that selection consumes, and emitting degraded/recovered
+    // events as conns cross the configured thresholds. A conn that stays bad
+    // past `close_miss_count` is torn down through the close-event path.
+    fn spawn_monitor(
+        peer_node_id: Uuid,
+        dispatcher: Arc<Dispatcher>,
+        global_ctx: ArcGlobalCtx,
+        close_event_sender: mpsc::Sender<Uuid>,
+        cfg: MonitorConfig,
+        shutdown: CancellationToken,
+    ) -> JoinHandle<()> {
+        tokio::spawn(
+            async move {
+                let epoch = Instant::now();
+                loop {
+                    select! {
+                        _ = shutdown.cancelled() => break,
+                        _ = tokio::time::sleep(cfg.sample_interval) => {}
+                    }
+
+                    let now_ms = epoch.elapsed().as_millis() as u64;
+                    let entries: Vec<(Uuid, ConnEntry)> = dispatcher
+                        .conns
+                        .iter()
+                        .map(|c| (*c.key(), c.value().clone()))
+                        .collect();
+
+                    for (conn_id, entry) in entries {
+                        let (stats, info) = {
+                            let guard = entry.conn.lock().await;
+                            (guard.get_stats(), guard.get_conn_info())
+                        };
+
+                        let measured = stats.latency_us > 0;
+                        if measured {
+                            entry.quality.record_rtt(stats.latency_us);
+                        }
+                        entry
+                            .quality
+                            .note_bytes(stats.tx_bytes, stats.rx_bytes, now_ms);
+
+                        // a conn with no measurement yet and no failed ping is
+                        // neither good nor bad -- don't penalise a freshly added
+                        // conn before its first pong.
+                        let ping_failed = stats.consecutive_ping_failures > 0;
+                        if !measured && !ping_failed {
+                            continue;
+                        }
+
+                        // a sample is bad if a ping went unanswered or the
+                        // smoothed RTT is above the degrade threshold.
+                        let bad = ping_failed
+                            || (measured && entry.quality.smoothed_rtt_us() > cfg.degrade_rtt_us);
+
+                        if !bad {
+                            entry.quality.reset_miss();
+                            // a good sample also clears the send-failure counter,
+                            // so a conn excluded from selection after repeated
+                            // send failures becomes eligible again once its link
+                            // is observably healthy -- otherwise it would stay
+                            // excluded forever, never getting a send to recover on.
+                            entry.quality.record_success();
+                            if entry.quality.is_degraded() {
+                                entry.quality.set_degraded(false);
+                                global_ctx
+                                    .issue_event(GlobalCtxEvent::PeerConnRecovered(info));
+                            }
+                            continue;
+                        }
+
+                        let misses = entry.quality.bump_miss();
+                        if misses >= cfg.close_miss_count {
+                            tracing::warn!(
+                                ?peer_node_id,
+                                ?conn_id,
+                                misses,
+                                "peer conn exceeded miss threshold, auto-closing",
+                            );
+                            let _ = close_event_sender.send(conn_id).await;
+                        } else if misses >= cfg.degrade_miss_count && !entry.quality.is_degraded()
+                        {
+                            entry.quality.set_degraded(true);
+                            global_ctx.issue_event(GlobalCtxEvent::PeerConnDegraded(info));
+                        }
+                    }
+                }
+                tracing::info!("peer {} monitor task exit", peer_node_id);
+            }
+            .instrument(tracing::info_span!("peer_monitor", ?peer_node_id)),
+        )
+    }
+
     pub async fn add_peer_conn(&self, mut conn: PeerConn) {
         conn.set_close_event_sender(self.close_event_sender.clone());
-        conn.start_recv_loop(self.packet_recv_chan.clone());
-        conn.start_pingpong();
+        conn.start_recv_loop(self.chunk_recv_chan.clone());
+        // the pingpong loop piggybacks our cumulative ack on each ping and
+        // forwards the remote's echoed ack back through `ack_event_sender`.
+        conn.start_pingpong(
+            self.reliable.outbound_ack.clone(),
+            self.ack_event_sender.clone(),
+        );
         self.global_ctx
             .issue_event(GlobalCtxEvent::PeerConnAdded(conn.get_conn_info()));
-        self.conns
-            .insert(conn.get_conn_id(), Arc::new(Mutex::new(conn)));
+
+        let conn_id = conn.get_conn_id();
+        let entry = ConnEntry {
+            conn: Arc::new(Mutex::new(conn)),
+            quality: Arc::new(ConnQuality::new()),
+            draining: Arc::new(AtomicBool::new(false)),
+        };
+        self.dispatcher.conns.insert(conn_id, entry);
+    }
+
+    pub fn set_default_policy(&self, policy: SelectionPolicy) {
+        self.dispatcher
+            .default_policy
+            .store(policy.as_u8(), Ordering::Relaxed);
     }
 
     pub async fn send_msg(&self, msg: Bytes) -> Result<(), Error> {
-        let Some(conn) = self.conns.iter().next() else {
+        self.send_msg_with_priority(msg, Priority::Normal).await
+    }
+
+    // Note: once a packet is accepted into the scheduler queue, the actual
+    // conn selection and the per-chunk retry-on-next-best-conn happen in the
+    // writer task, so a conn failure after this point is handled there, not
+    // surfaced here. The synchronous outcome this returns is therefore the
+    // pre-flight check: `PeerNoConnectionError` when the peer has no usable conn,
+    // or a full send queue applying backpressure.
+    pub async fn send_msg_with_priority(&self, msg: Bytes, prio: Priority) -> Result<(), Error> {
+        if !self.dispatcher.has_usable_conn() {
             return Err(Error::PeerNoConnectionError(self.peer_node_id));
-        };
+        }
+        self.scheduler.enqueue(prio, frame_plain(&msg)).await
+    }
 
-        let conn_clone = conn.clone();
-        drop(conn);
-        conn_clone.lock().await.send_msg(msg).await?;
+    // Send `msg` reliably: it is buffered and retransmitted until the remote
+    // acknowledges it. The returned future resolves once that ack arrives, or
+    // errors if no ack lands within `RELIABLE_SEND_DEADLINE` (e.g. the only conn
+    // died permanently). Blocks while the in-flight window is full, applying
+    // backpressure.
+    pub async fn send_msg_reliable(&self, msg: Bytes) -> Result<(), Error> {
+        let permit = self
+            .reliable
+            .permits
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|_| Error::PeerNoConnectionError(self.peer_node_id))?;
+        let seq = self.reliable.next_seq.fetch_add(1, Ordering::Relaxed);
+        let frame = frame_reliable(seq, &msg);
 
-        Ok(())
+        let (tx, rx) = oneshot::channel();
+        {
+            let mut map = self.reliable.in_flight.lock().await;
+            map.insert(
+                seq,
+                InFlight {
+                    frame: frame.clone(),
+                    last_sent: Instant::now(),
+                    waiter: Some(tx),
+                    _permit: permit,
+                },
+            );
+        }
+
+        self.scheduler.enqueue(Priority::Normal, frame).await?;
+        match tokio::time::timeout(RELIABLE_SEND_DEADLINE, rx).await {
+            Ok(Ok(())) => Ok(()),
+            // waiter dropped without an ack, or the deadline elapsed: abandon the
+            // message, freeing its in-flight slot, and report the failure.
+            Ok(Err(_)) | Err(_) => {
+                self.reliable.in_flight.lock().await.remove(&seq);
+                Err(Error::PeerNoConnectionError(self.peer_node_id))
+            }
+        }
+    }
+
+    pub fn set_drain_timeout(&mut self, timeout: std::time::Duration) {
+        self.drain_timeout = timeout;
     }
 
     pub async fn close_peer_conn(&self, conn_id: &Uuid) -> Result<(), Error> {
-        let has_key = self.conns.contains_key(conn_id);
-        if !has_key {
+        let Some(entry) = self.dispatcher.conns.get(conn_id).map(|e| e.value().clone()) else {
             return Err(Error::NotFound);
-        }
+        };
+
+        // mark the conn draining so `send_msg` stops selecting it, then let any
+        // in-flight writes finish before we actually tear it down.
+        entry.draining.store(true, Ordering::Relaxed);
+        self.drain_conn(conn_id, &entry).await;
+
+        // removal and `PeerConnRemoved` still flow through the close-event path.
         self.close_event_sender.send(conn_id.clone()).await.unwrap();
         Ok(())
     }
 
+    // Flush queued traffic, then wait for the conn's in-flight write to finish,
+    // before removal. Sends are centralized in one scheduler/writer -- there is
+    // no per-conn send queue -- so the first step drains the peer's shared
+    // queues (chunks already accepted may still select this conn), and the
+    // second acquires the conn lock a send holds for its duration, proving no
+    // write is still in flight on it. The whole barrier is bounded by
+    // `drain_timeout`.
+    async fn drain_conn(&self, conn_id: &Uuid, entry: &ConnEntry) {
+        let scheduler = self.scheduler.clone();
+        let barrier = async {
+            while !scheduler.is_drained() {
+                tokio::time::sleep(DRAIN_POLL_INTERVAL).await;
+            }
+            let _guard = entry.conn.lock().await;
+        };
+        match tokio::time::timeout(self.drain_timeout, barrier).await {
+            Ok(()) => {
+                tracing::debug!(?conn_id, "peer conn drained, in-flight writes complete");
+            }
+            Err(_) => {
+                tracing::warn!(?conn_id, "peer conn drain timed out, closing anyway");
+            }
+        }
+    }
+
     pub async fn list_peer_conns(&self) -> Vec<PeerConnInfo> {
-        let mut conns = vec![];
-        for conn in self.conns.iter() {
+        let mut entries = vec![];
+        for entry in self.dispatcher.conns.iter() {
             // do not lock here, otherwise it will cause dashmap deadlock
-            conns.push(conn.clone());
+            entries.push(entry.value().clone());
         }
 
         let mut ret = Vec::new();
-        for conn in conns {
-            ret.push(conn.lock().await.get_conn_info());
+        for entry in entries {
+            let mut info = entry.conn.lock().await.get_conn_info();
+            // overlay the monitor's live quality view so callers (a UI, or the
+            // selection logic) see smoothed RTT, throughput and health.
+            entry.quality.fill_info(&mut info);
+            ret.push(info);
         }
         ret
     }
@@ -145,7 +1284,7 @@ impl Peer {
 // pritn on drop
 impl Drop for Peer {
     fn drop(&mut self) {
-        self.shutdown_notifier.notify_one();
+        self.shutdown.cancel();
         tracing::info!("peer {} drop", self.peer_node_id);
     }
 }
@@ -216,3 +1355,100 @@ mod tests {
         close_handler.await.unwrap().unwrap();
     }
 }
+
+#[cfg(test)]
+mod logic_tests {
+    use super::*;
+
+    // chunk a multi-chunk message and reassemble it, feeding the chunks back in
+    // reverse order to exercise the offset-based reordering.
+    #[test]
+    fn chunk_reassembly_round_trip() {
+        let msg = Bytes::from((0..40_000u32).map(|i| i as u8).collect::<Vec<_>>());
+        let mut chunks = chunk_msg(7, &msg);
+        assert!(chunks.len() > 1, "message should span several chunks");
+        chunks.reverse();
+
+        let mut reasm = Reassembler::new();
+        let mut out = None;
+        for chunk in chunks {
+            if let Some(done) = reasm.push(chunk, 0) {
+                out = Some(done);
+            }
+        }
+        assert_eq!(out.as_ref(), Some(&msg));
+    }
+
+    // a single-chunk message completes immediately, and a redundant duplicate of
+    // an already-delivered message is dropped rather than delivered twice.
+    #[test]
+    fn reassembly_dedups_redundant_copies() {
+        let msg = Bytes::from_static(b"hello");
+        let chunks = chunk_msg(11, &msg);
+        assert_eq!(chunks.len(), 1);
+
+        let mut reasm = Reassembler::new();
+        assert_eq!(reasm.push(chunks[0].clone(), 0), Some(msg));
+        assert_eq!(reasm.push(chunks[0].clone(), 0), None);
+    }
+
+    // a partial whose LAST chunk never arrives is evicted once it ages past the
+    // TTL instead of leaking forever.
+    #[test]
+    fn stale_partials_are_evicted() {
+        let msg = Bytes::from((0..40_000u32).map(|i| i as u8).collect::<Vec<_>>());
+        let chunks = chunk_msg(3, &msg);
+
+        let mut reasm = Reassembler::new();
+        // only the first (non-final) chunk arrives.
+        assert_eq!(reasm.push(chunks[0].clone(), 0), None);
+        assert!(reasm.partial.contains_key(&3));
+
+        // a later chunk for a different message, arriving past the TTL, sweeps it.
+        let other = chunk_msg(4, &msg);
+        reasm.push(other[0].clone(), PARTIAL_TTL_MS + 1);
+        assert!(!reasm.partial.contains_key(&3));
+    }
+
+    #[tokio::test]
+    async fn reliable_delivers_in_order() {
+        let p = |n: u8| Bytes::copy_from_slice(&[n]);
+        let state = ReliableState::new();
+        // seq 1 delivers immediately.
+        assert_eq!(state.on_received(1, p(1)).await, (1, vec![p(1)]));
+        // a gap: 3 is buffered, not delivered, and does not advance the watermark.
+        assert_eq!(state.on_received(3, p(3)).await, (1, vec![]));
+        // 2 fills the gap and flushes the buffered 3, in order.
+        assert_eq!(state.on_received(2, p(2)).await, (3, vec![p(2), p(3)]));
+        // a duplicate at or below the watermark is not delivered again.
+        assert_eq!(state.on_received(2, p(2)).await, (3, vec![]));
+    }
+
+    #[tokio::test]
+    async fn process_ack_wakes_waiters() {
+        let state = ReliableState::new();
+        let (tx, rx) = oneshot::channel();
+        let permit = state.permits.clone().acquire_owned().await.unwrap();
+        state.in_flight.lock().await.insert(
+            5,
+            InFlight {
+                frame: Bytes::new(),
+                last_sent: Instant::now(),
+                waiter: Some(tx),
+                _permit: permit,
+            },
+        );
+
+        state.process_ack(5).await;
+        assert!(rx.await.is_ok(), "ack should resolve the waiter");
+        assert!(state.in_flight.lock().await.is_empty());
+    }
+
+    // the retransmit timeout tracks the best RTT but stays within its bounds.
+    #[test]
+    fn reliable_timeout_is_clamped() {
+        assert_eq!(reliable_timeout(None), RELIABLE_TIMEOUT_DEFAULT);
+        assert_eq!(reliable_timeout(Some(1)), RELIABLE_TIMEOUT_MIN);
+        assert_eq!(reliable_timeout(Some(u64::MAX)), RELIABLE_TIMEOUT_MAX);
+    }
+}
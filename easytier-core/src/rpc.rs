@@ -0,0 +1,45 @@
+// Data transfer objects surfaced over the management RPC / CLI. `PeerConnInfo`
+// is the snapshot a caller sees for a single `PeerConn`; alongside the static
+// handshake metadata it now carries the live per-conn quality stats maintained
+// by the health monitor so a UI (or the selection logic) can reason about link
+// health without reaching into the peer internals.
+
+use uuid::Uuid;
+
+/// A point-in-time view of one `PeerConn`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PeerConnInfo {
+    /// Stable id of this conn.
+    pub conn_id: Uuid,
+    /// Node id on our side of the link.
+    pub my_peer_id: Uuid,
+    /// Node id of the remote peer.
+    pub peer_id: Uuid,
+    /// Whether we opened the conn (client) or accepted it (server).
+    pub is_client: bool,
+
+    /// Live quality stats, filled in by [`crate::peers::peer::Peer::list_peer_conns`]
+    /// from the per-conn health monitor. Fields read zero on a conn that has not
+    /// been sampled yet.
+    pub stats: PeerConnStats,
+}
+
+/// Live per-conn quality, sampled by the health monitor. Smoothed RTT, byte
+/// counters and the degraded flag let consumers rank or avoid a flaky link.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PeerConnStats {
+    /// Latest RTT measured by the pingpong loop, in micros (0 means unmeasured,
+    /// i.e. no pong yet -- distinct from a ping that failed).
+    pub latency_us: u64,
+    /// Consecutive unanswered pings on this conn; 0 while the link is responsive.
+    pub consecutive_ping_failures: u32,
+    /// EWMA-smoothed RTT in micros (0 until measured).
+    pub smoothed_rtt_us: u64,
+    /// Total bytes sent / received on this conn.
+    pub tx_bytes: u64,
+    pub rx_bytes: u64,
+    /// Consecutive bad monitor samples; non-zero means the conn is struggling.
+    pub consecutive_misses: u32,
+    /// Set once the conn has crossed the degrade threshold.
+    pub degraded: bool,
+}